@@ -1,9 +1,11 @@
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
 
+use base64::Engine;
 use clap::{Parser, Subcommand};
 use futures::stream::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
@@ -13,8 +15,10 @@ use reqwest::header::{self, HeaderValue};
 use reqwest::Client;
 use scraper::element_ref::ElementRef;
 use scraper::{Html, Selector};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tempfile::tempdir;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
 
 type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
@@ -28,20 +32,38 @@ struct Args {
     command: Command,
     #[arg(short, long, default_value = "posts")]
     directory: PathBuf,
+    #[arg(long, value_enum, default_value = "directory")]
+    format: OutputFormat,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Directory,
+    Epub,
+    Monolith,
+    Cbz,
+    Zip,
 }
 
 #[derive(Subcommand)]
 enum Command {
     Member {
-        #[arg(default_value = "29156514")]
-        id: String,
+        ids: Vec<String>,
         #[arg(short, long)]
         filter: Option<String>,
         #[arg(short, long)]
         limit: Option<usize>,
+        /// Read additional member IDs from a file (one per line, `#` comments
+        /// and blank lines skipped), or `-` for stdin
+        #[arg(short, long)]
+        input_file: Option<String>,
     },
     Url {
         urls: Vec<String>,
+        /// Read additional URLs from a file (one per line, `#` comments and
+        /// blank lines skipped), or `-` for stdin
+        #[arg(short, long)]
+        input_file: Option<String>,
     },
 }
 
@@ -54,13 +76,28 @@ async fn main() -> Result<()> {
         .build()
         .unwrap();
 
+    let mut manifest = Manifest::load(&args.directory)?;
+
     match args.command {
-        Command::Url { urls } => {
+        Command::Url { mut urls, input_file } => {
+            if let Some(file) = input_file {
+                urls.extend(read_lines_from_source(&file)?);
+            }
             for url in urls {
-                process_one(&client, &url, &args.directory).await?;
+                process_one(&client, &url, &args.directory, args.format, &mut manifest).await?;
             }
         }
-        Command::Member { id, filter, limit } => {
+        Command::Member {
+            mut ids,
+            filter,
+            limit,
+            input_file,
+        } => {
+            if let Some(file) = input_file {
+                ids.extend(read_lines_from_source(&file)?);
+            } else if ids.is_empty() {
+                ids.push("29156514".to_owned());
+            }
             let filter = match filter {
                 Some(f) => RegexBuilder::new(&f),
                 None => RegexBuilder::new(r".*"),
@@ -68,13 +105,100 @@ async fn main() -> Result<()> {
             .case_insensitive(true)
             .build()?;
 
-            process_member(&client, &id, &args.directory, &filter, limit).await?;
+            for id in ids {
+                process_member(
+                    &client,
+                    &id,
+                    &args.directory,
+                    &filter,
+                    limit,
+                    args.format,
+                    &mut manifest,
+                )
+                .await?;
+            }
         }
     }
 
     Ok(())
 }
 
+/// Tracks which volumes have already been downloaded, keyed by volume ID, so
+/// repeat runs can skip completed posts without trusting directory/file
+/// existence (which can't distinguish a complete download from a partial one
+/// left behind by an interrupted run).
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    volumes: HashMap<String, ManifestEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestEntry {
+    title: String,
+    date: String,
+    image_count: usize,
+    complete: bool,
+}
+
+impl Manifest {
+    fn load(directory: &Path) -> Result<Manifest> {
+        let manifest_path = Self::path(directory);
+        if !manifest_path.exists() {
+            return Ok(Manifest::default());
+        }
+        let data = std::fs::read_to_string(manifest_path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    fn save(&self, directory: &Path) -> Result<()> {
+        std::fs::create_dir_all(directory)?;
+        let data = serde_json::to_string_pretty(self)?;
+        let temp_file = tempfile::NamedTempFile::new_in(directory)?;
+        std::fs::write(temp_file.path(), data)?;
+        temp_file.persist(Self::path(directory))?;
+        Ok(())
+    }
+
+    fn is_complete(&self, id: &str) -> bool {
+        self.volumes.get(id).map(|e| e.complete).unwrap_or(false)
+    }
+
+    fn mark_complete(&mut self, id: &str, title: &str, date: &str, image_count: usize) {
+        self.volumes.insert(
+            id.to_owned(),
+            ManifestEntry {
+                title: title.to_owned(),
+                date: date.to_owned(),
+                image_count,
+                complete: true,
+            },
+        );
+    }
+
+    fn path(directory: &Path) -> PathBuf {
+        directory.join(".archive-np.json")
+    }
+}
+
+/// Reads one target (URL or member ID) per line from a file, or from stdin
+/// when `source` is `-`. Blank lines and `#`-prefixed comments are skipped.
+fn read_lines_from_source(source: &str) -> Result<Vec<String>> {
+    let content = if source == "-" {
+        let mut buf = String::new();
+        std::io::stdin().read_to_string(&mut buf)?;
+        buf
+    } else {
+        std::fs::read_to_string(source)?
+    };
+
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect())
+}
+
 #[derive(Debug)]
 enum DownloadNPError {
     ParseError(String),
@@ -95,7 +219,6 @@ impl fmt::Display for DownloadNPError {
 #[derive(Debug)]
 struct Volume {
     title: Option<String>,
-    date: Option<String>,
     id: String,
 }
 
@@ -105,20 +228,22 @@ impl PartialEq for Volume {
     }
 }
 
-async fn process_one(client: &Client, url: &str, path: &Path) -> Result<()> {
+async fn process_one(
+    client: &Client,
+    url: &str,
+    path: &Path,
+    format: OutputFormat,
+    manifest: &mut Manifest,
+) -> Result<()> {
     let id = ID_RE
         .captures_iter(url)
         .find_map(|c| c.name("vol"))
         .ok_or_else(|| DownloadNPError::ParseError(url.to_owned()))?
         .as_str()
         .to_owned();
-    let vol = Volume {
-        id,
-        title: None,
-        date: None,
-    };
+    let vol = Volume { id, title: None };
 
-    download_np(client, &vol, path).await?;
+    download_np(client, &vol, path, format, manifest).await?;
     Ok(())
 }
 
@@ -128,6 +253,8 @@ async fn process_member(
     path: &Path,
     filter: &Regex,
     limit: Option<usize>,
+    format: OutputFormat,
+    manifest: &mut Manifest,
 ) -> Result<()> {
     let mut page: usize = 1;
     let mut first = true;
@@ -145,6 +272,10 @@ async fn process_member(
         first = false;
         let page_np_vols = volume_from_member(client, member, page).await?;
         num_found = page_np_vols.len();
+        // every volume on this page is already archived: older pages will
+        // be too, so there's no point paging further back
+        let page_fully_known =
+            num_found > 0 && page_np_vols.iter().all(|vol| manifest.is_complete(&vol.id));
         np_vols.extend(page_np_vols);
         np_vols.dedup();
         page += 1;
@@ -154,6 +285,9 @@ async fn process_member(
                 break;
             }
         }
+        if page_fully_known {
+            break;
+        }
     }
     pb.finish_and_clear();
     np_vols.retain(|vol| match &vol.title {
@@ -162,7 +296,7 @@ async fn process_member(
     });
 
     for vol in np_vols {
-        download_np(client, &vol, path).await?;
+        download_np(client, &vol, path, format, manifest).await?;
     }
 
     Ok(())
@@ -172,7 +306,6 @@ async fn volume_from_member(client: &Client, member: &str, page: usize) -> Resul
     lazy_static! {
         static ref SEL: Selector = Selector::parse("li").unwrap();
         static ref TITLE_SEL: Selector = Selector::parse(".tit_feed").unwrap();
-        static ref DATE_SEL: Selector = Selector::parse(".date_post").unwrap();
         static ref ESCAPE_RE: Regex = Regex::new(r#"\\(?P<c>[^"n])"#).unwrap();
     }
     const URL: &str = "https://post.naver.com/async/my.nhn";
@@ -216,22 +349,8 @@ async fn volume_from_member(client: &Client, member: &str, page: usize) -> Resul
                         .to_owned()
                 });
 
-            // get date
-            let date = Html::parse_fragment(&e.inner_html())
-                .select(&DATE_SEL)
-                .next()
-                .map(|v| {
-                    v.text()
-                        .collect::<Vec<_>>()
-                        .join("")
-                        .replace('.', "")
-                        .trim()
-                        .to_owned()
-                });
-
             let ret = Volume {
                 title,
-                date,
                 id: String::from(id),
             };
             Some(ret)
@@ -241,18 +360,30 @@ async fn volume_from_member(client: &Client, member: &str, page: usize) -> Resul
     Ok(ret)
 }
 
-async fn download_np(client: &Client, vol: &Volume, path: &Path) -> Result<()> {
-    // check if already downloaded
-    if vol.title.is_some() && vol.date.is_some() {
-        let date = vol.date.as_ref().unwrap();
-        let title = vol.title.as_ref().unwrap();
+// the destination path for a volume under the given output format
+fn output_path(path: &Path, format: OutputFormat, date: &str, id: &str, title: &str) -> PathBuf {
+    match format {
+        OutputFormat::Directory => path.join(format!("{}-{}-{}/", date, id, title)),
+        OutputFormat::Epub => path.join(format!("{}-{}-{}.epub", date, id, title)),
+        OutputFormat::Monolith => path.join(format!("{}-{}-{}.html", date, id, title)),
+        OutputFormat::Cbz => path.join(format!("{}-{}-{}.cbz", date, id, title)),
+        OutputFormat::Zip => path.join(format!("{}-{}-{}.zip", date, id, title)),
+    }
+}
 
-        if date.chars().all(|c: char| c.is_ascii_digit()) {
-            let full_path = path.join(format!("{}-{}-{}/", date, vol.id, title));
-            if full_path.exists() {
-                return Ok(());
-            }
-        }
+async fn download_np(
+    client: &Client,
+    vol: &Volume,
+    path: &Path,
+    format: OutputFormat,
+    manifest: &mut Manifest,
+) -> Result<()> {
+    // check if already downloaded; the manifest is only updated once a
+    // download fully completes (or the post turns out to have nothing to
+    // download), so a partial download from an interrupted run is correctly
+    // retried rather than treated as done
+    if manifest.is_complete(&vol.id) {
+        return Ok(());
     }
 
     // fetch page
@@ -274,19 +405,40 @@ async fn download_np(client: &Client, vol: &Volume, path: &Path) -> Result<()> {
     let date = extract_date(&document.root_element())?;
     let title = extract_title(&document.root_element())?;
 
-    // check if already downloaded
-    let full_path = path.join(format!("{}-{}-{}/", date, vol.id, title));
-    if full_path.exists() {
-        return Ok(());
-    }
-
-    // extract images
-    let imgs = extract_images(&root)?;
+    let full_path = output_path(path, format, &date, &vol.id, &title);
+
+    // extract the post body: interleaved text and image placeholders, in
+    // reading order, falling back to the plain image scrape when no image is
+    // found in se_component blocks (e.g. the page isn't wrapped in them)
+    let mut content = extract_content(&root);
+    let mut imgs: Vec<String> = content
+        .iter()
+        .filter_map(|c| match c {
+            ContentPiece::Image(url) => Some(url.clone()),
+            ContentPiece::Text(_) => None,
+        })
+        .collect();
     if imgs.is_empty() {
-        println!("No images found for vol: {}", vol.id);
+        // no image in se_component blocks (the page may not be wrapped in
+        // them, or they hold only text); fall back to the plain image scrape,
+        // appending what it finds after any text, and make the images part of
+        // the rendered content so
+        // renderers don't download/embed bytes that are never referenced
+        imgs = extract_images(&root)?;
+        content.extend(imgs.iter().cloned().map(ContentPiece::Image));
+    }
+    if imgs.is_empty() && !content.iter().any(|c| matches!(c, ContentPiece::Text(_))) {
+        println!("No images or text found for vol: {}", vol.id);
+        // still mark it complete (with no images) so a member whose history
+        // has a handful of unparseable posts (deleted/private/video-only)
+        // doesn't defeat page_fully_known and force a full re-crawl forever
+        manifest.mark_complete(&vol.id, &title, &date, 0);
+        manifest.save(path)?;
         return Ok(());
     }
 
+    let image_count = imgs.len();
+
     // create base directory if it doesn't exist
     let _ = std::fs::create_dir_all(path);
 
@@ -297,21 +449,95 @@ async fn download_np(client: &Client, vol: &Volume, path: &Path) -> Result<()> {
         .progress_chars("=> ");
     pb.set_style(sty);
 
-    // download all images
     println!("{}...", title);
-    let temp_dir = tempdir()?;
-    futures::stream::iter(imgs.into_iter().enumerate().map(|(i, url)| {
-        let ext = extract_extension(&url);
-        let filename = format!("{}-{}-{}-img{:03}{}", date, vol.id, title, i + 1, ext);
-        download_image(client, url, temp_dir.path().join(filename), &pb)
-    }))
-    .buffer_unordered(20)
-    .collect::<Vec<_>>()
-    .await
-    .into_iter()
-    .collect::<Result<_>>()?;
+    if format == OutputFormat::Monolith {
+        // monolith inlines images as data: URLs, so keep the bytes in
+        // memory keyed by URL instead of writing them to disk
+        let image_bytes = download_images_to_memory(client, &imgs, &pb).await?;
+        write_monolith(&content, &image_bytes, &title, &date, &full_path)?;
+    } else {
+        // download all images into a temp dir, named in reading order
+        let temp_dir = tempdir()?;
+        let filenames = imgs
+            .iter()
+            .enumerate()
+            .map(|(i, url)| {
+                let ext = extract_extension(url);
+                temp_dir.path().join(format!(
+                    "{}-{}-{}-img{:03}{}",
+                    date,
+                    vol.id,
+                    title,
+                    i + 1,
+                    ext
+                ))
+            })
+            .collect::<Vec<_>>();
+        futures::stream::iter(
+            imgs.into_iter()
+                .zip(filenames.iter().cloned())
+                .map(|(url, filename)| download_image(client, url, filename, &pb)),
+        )
+        .buffer_unordered(20)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<_>>()?;
+
+        match format {
+            OutputFormat::Directory => {
+                write_content_md(&temp_dir, &content, &filenames)?;
+                write_directory(&temp_dir, path, &full_path)?
+            }
+            OutputFormat::Epub => write_epub(&content, &filenames, &title, &date, &full_path)?,
+            OutputFormat::Cbz | OutputFormat::Zip => write_archive(&temp_dir, &full_path)?,
+            OutputFormat::Monolith => unreachable!(),
+        }
+    }
+
+    // only mark the volume complete now that the write above succeeded, so
+    // an interrupted run is retried instead of silently treated as done
+    manifest.mark_complete(&vol.id, &title, &date, image_count);
+    manifest.save(path)?;
+
+    pb.finish_and_clear();
+
+    Ok(())
+}
+
+/// Writes `content.md`, the post's text interleaved with its images in
+/// reading order, into the temp dir alongside the downloaded images.
+fn write_content_md(
+    temp_dir: &tempfile::TempDir,
+    content: &[ContentPiece],
+    filenames: &[PathBuf],
+) -> Result<()> {
+    let mut markdown = String::new();
+    let mut images = filenames.iter();
+    for piece in content {
+        match piece {
+            ContentPiece::Text(text) => {
+                markdown.push_str(text);
+                markdown.push_str("\n\n");
+            }
+            ContentPiece::Image(_) => {
+                if let Some(filename) = images.next() {
+                    let name = filename.file_name().unwrap_or_default().to_string_lossy();
+                    markdown.push_str(&format!("![]({})\n\n", name));
+                }
+            }
+        }
+    }
+
+    let mut file = File::create(temp_dir.path().join("content.md"))?;
+    file.write_all(markdown.as_bytes())?;
+    Ok(())
+}
 
-    // move temp directory
+// moves the temp dir of downloaded images into place as full_path; writing
+// to a temp location first and renaming means a partial download never
+// leaves a corrupt entry behind
+fn write_directory(temp_dir: &tempfile::TempDir, path: &Path, full_path: &Path) -> Result<()> {
     let options = fs_extra::dir::CopyOptions::new();
     let temp_dir_2 = path.join(
         temp_dir
@@ -319,22 +545,315 @@ async fn download_np(client: &Client, vol: &Volume, path: &Path) -> Result<()> {
             .file_name()
             .ok_or_else(|| DownloadNPError::FileNameError(temp_dir.path().to_path_buf()))?,
     );
-    fs_extra::dir::copy(&temp_dir, path, &options)?;
-    std::fs::rename(&temp_dir_2, &full_path)?;
+    fs_extra::dir::copy(temp_dir, path, &options)?;
+    std::fs::rename(&temp_dir_2, full_path)?;
+    Ok(())
+}
 
-    pb.finish_and_clear();
+// streams the downloaded images into a zip (cbz and zip only differ in
+// extension); each file is copied straight from disk into the zip writer
+// rather than read into memory up front, so large posts don't blow up memory
+fn write_archive(temp_dir: &tempfile::TempDir, full_path: &Path) -> Result<()> {
+    let temp_file = tempfile::NamedTempFile::new_in(
+        full_path
+            .parent()
+            .ok_or_else(|| DownloadNPError::FileNameError(full_path.to_path_buf()))?,
+    )?;
+
+    let mut zip = ZipWriter::new(temp_file.reopen()?);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut entries = std::fs::read_dir(temp_dir.path())?.collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        zip.start_file(entry.file_name().to_string_lossy(), options)?;
+        std::io::copy(&mut File::open(entry.path())?, &mut zip)?;
+    }
+    zip.finish()?;
+    temp_file.persist(full_path)?;
 
     Ok(())
 }
 
+// packages the post's text and images as a single-chapter EPUB
+fn write_epub(
+    content: &[ContentPiece],
+    images: &[PathBuf],
+    title: &str,
+    date: &str,
+    full_path: &Path,
+) -> Result<()> {
+    // title/date come straight from the post's HTML (already entity-decoded
+    // by scraper) and post text can contain arbitrary prose, so all of it
+    // must be re-escaped before going into XML or the EPUB is malformed
+    let title = escape_xml(title);
+    let date = escape_xml(date);
+    let title = title.as_str();
+    let date = date.as_str();
+
+    let temp_file = tempfile::NamedTempFile::new_in(
+        full_path
+            .parent()
+            .ok_or_else(|| DownloadNPError::FileNameError(full_path.to_path_buf()))?,
+    )?;
+
+    let mut zip = ZipWriter::new(temp_file.reopen()?);
+
+    // the mimetype entry must be first and stored uncompressed per the EPUB spec
+    zip.start_file(
+        "mimetype",
+        FileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(b"application/epub+zip")?;
+
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file("META-INF/container.xml", options)?;
+    zip.write_all(
+        br#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#,
+    )?;
+
+    let mut manifest_items = String::new();
+    for (i, image) in images.iter().enumerate() {
+        let ext = image
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let media_type = image_media_type(&ext);
+        let name = format!("img{:03}.{}", i + 1, ext);
+
+        zip.start_file(format!("OEBPS/images/{}", name), options)?;
+        let mut buf = Vec::new();
+        File::open(image)?.read_to_end(&mut buf)?;
+        zip.write_all(&buf)?;
+
+        manifest_items.push_str(&format!(
+            "    <item id=\"img{:03}\" href=\"images/{}\" media-type=\"{}\"/>\n",
+            i + 1,
+            name,
+            media_type
+        ));
+    }
+
+    // interleave the post's text and images in reading order
+    let mut body = String::new();
+    let mut image_names = images.iter().enumerate();
+    for piece in content {
+        match piece {
+            ContentPiece::Text(text) => {
+                body.push_str(&format!("<p>{}</p>\n", escape_xml(text)));
+            }
+            ContentPiece::Image(_) => {
+                if let Some((i, _)) = image_names.next() {
+                    body.push_str(&format!(
+                        "<img src=\"images/img{:03}.{}\" alt=\"\"/>\n",
+                        i + 1,
+                        images[i]
+                            .extension()
+                            .map(|e| e.to_string_lossy().to_lowercase())
+                            .unwrap_or_default()
+                    ));
+                }
+            }
+        }
+    }
+
+    zip.start_file("OEBPS/content.xhtml", options)?;
+    zip.write_all(
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml">
+<head><title>{title}</title></head>
+<body>
+<h1>{title}</h1>
+<p>{date}</p>
+{body}</body>
+</html>
+"#,
+            title = title,
+            date = date,
+            body = body
+        )
+        .as_bytes(),
+    )?;
+
+    zip.start_file("OEBPS/content.opf", options)?;
+    zip.write_all(
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" unique-identifier="BookId" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>{title}</dc:title>
+    <dc:date>{date}</dc:date>
+    <dc:identifier id="BookId">{date}-{title}</dc:identifier>
+    <dc:language>ko</dc:language>
+  </metadata>
+  <manifest>
+    <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+    <item id="content" href="content.xhtml" media-type="application/xhtml+xml"/>
+{manifest_items}  </manifest>
+  <spine toc="ncx">
+    <itemref idref="content"/>
+  </spine>
+</package>
+"#,
+            title = title,
+            date = date,
+            manifest_items = manifest_items
+        )
+        .as_bytes(),
+    )?;
+
+    zip.start_file("OEBPS/toc.ncx", options)?;
+    zip.write_all(
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
+  <head>
+    <meta name="dtb:uid" content="{title}"/>
+  </head>
+  <docTitle><text>{title}</text></docTitle>
+  <navMap>
+    <navPoint id="content" playOrder="1">
+      <navLabel><text>{title}</text></navLabel>
+      <content src="content.xhtml"/>
+    </navPoint>
+  </navMap>
+</ncx>
+"#,
+            title = title
+        )
+        .as_bytes(),
+    )?;
+
+    zip.finish()?;
+    temp_file.persist(full_path)?;
+
+    Ok(())
+}
+
+// inlines every image as a base64 data: URL for a single self-contained
+// .html file with no external asset dependencies
+fn write_monolith(
+    content: &[ContentPiece],
+    image_bytes: &HashMap<String, Vec<u8>>,
+    title: &str,
+    date: &str,
+    full_path: &Path,
+) -> Result<()> {
+    // title/date/text are arbitrary post content and must be escaped before
+    // interpolation, same as the EPUB renderer
+    let title = escape_xml(title);
+    let date = escape_xml(date);
+    let title = title.as_str();
+    let date = date.as_str();
+
+    let mut body = String::new();
+    for piece in content {
+        match piece {
+            ContentPiece::Text(text) => body.push_str(&format!("<p>{}</p>\n", escape_xml(text))),
+            ContentPiece::Image(url) => {
+                if let Some(bytes) = image_bytes.get(url) {
+                    let ext = extract_extension(url);
+                    let media_type = image_media_type(ext.trim_start_matches('.'));
+                    let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+                    body.push_str(&format!(
+                        "<img src=\"data:{};base64,{}\" alt=\"\"/>\n",
+                        media_type, encoded
+                    ));
+                }
+            }
+        }
+    }
+
+    let html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"/><title>{title}</title></head>
+<body>
+<h1>{title}</h1>
+<p>{date}</p>
+{body}</body>
+</html>
+"#,
+        title = title,
+        date = date,
+        body = body
+    );
+
+    let temp_file = tempfile::NamedTempFile::new_in(
+        full_path
+            .parent()
+            .ok_or_else(|| DownloadNPError::FileNameError(full_path.to_path_buf()))?,
+    )?;
+    std::fs::write(temp_file.path(), html.as_bytes())?;
+    temp_file.persist(full_path)?;
+
+    Ok(())
+}
+
+// escapes markup-significant characters so post text can be safely
+// interpolated into generated XML/HTML
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn image_media_type(ext: &str) -> &'static str {
+    match ext {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        _ => "application/octet-stream",
+    }
+}
+
 async fn download_image(
     client: &Client,
     url: String,
     path: PathBuf,
     pb: &ProgressBar,
 ) -> Result<()> {
+    let body = fetch_image_bytes(client, &url).await?;
+    let mut buffer = File::create(path)?;
+    buffer.write_all(&body)?;
+    pb.inc(1);
+    Ok(())
+}
+
+// fetches every image concurrently, keeping bytes in memory keyed by URL
+// instead of writing them to disk
+async fn download_images_to_memory(
+    client: &Client,
+    urls: &[String],
+    pb: &ProgressBar,
+) -> Result<HashMap<String, Vec<u8>>> {
+    futures::stream::iter(urls.iter().cloned().map(|url| async move {
+        let bytes = fetch_image_bytes(client, &url).await?;
+        pb.inc(1);
+        Ok::<_, Box<dyn Error>>((url, bytes))
+    }))
+    .buffer_unordered(20)
+    .collect::<Vec<_>>()
+    .await
+    .into_iter()
+    .collect()
+}
+
+async fn fetch_image_bytes(client: &Client, url: &str) -> Result<Vec<u8>> {
     let body = client
-        .get(&url)
+        .get(url)
         .header(
             header::REFERER,
             HeaderValue::from_static("https://m.post.naver.com/"),
@@ -344,10 +863,7 @@ async fn download_image(
         .error_for_status()?
         .bytes()
         .await?;
-    let mut buffer = File::create(path)?;
-    buffer.write_all(&body)?;
-    pb.inc(1);
-    Ok(())
+    Ok(body.to_vec())
 }
 
 fn extract_extension(url: &str) -> String {
@@ -412,15 +928,7 @@ fn extract_images(element: &ElementRef) -> Result<Vec<String>> {
     let find_images = |sel: &Selector| {
         element
             .select(sel)
-            .filter_map(|e| {
-                let url = e.value().attr("data-src")?;
-                if !url.contains("post-phinf.pstatic.net") {
-                    return None;
-                }
-                let mut temp = reqwest::Url::parse(url).ok()?;
-                temp.query_pairs_mut().clear();
-                Some(temp.as_str().trim_end_matches('?').to_owned())
-            })
+            .filter_map(|e| normalize_image_url(e.value().attr("data-src")?))
             .collect::<Vec<_>>()
     };
 
@@ -432,3 +940,56 @@ fn extract_images(element: &ElementRef) -> Result<Vec<String>> {
     let ret = find_images(&IMG_SEL_2);
     Ok(ret)
 }
+
+/// Normalizes a Naver Post image URL: rejects anything not served from
+/// `post-phinf.pstatic.net` and strips the (irrelevant for archival) query
+/// parameters so the same image doesn't get downloaded under different URLs.
+fn normalize_image_url(url: &str) -> Option<String> {
+    if !url.contains("post-phinf.pstatic.net") {
+        return None;
+    }
+    let mut temp = reqwest::Url::parse(url).ok()?;
+    temp.query_pairs_mut().clear();
+    Some(temp.as_str().trim_end_matches('?').to_owned())
+}
+
+/// A piece of a post's body, in reading order: either a run of text or an
+/// inline image placeholder.
+#[derive(Debug)]
+enum ContentPiece {
+    Text(String),
+    Image(String),
+}
+
+/// Walks the post body's `se_component` blocks in document order, emitting
+/// an interleaved sequence of text and image placeholders. This preserves
+/// the original reading order of text and images, unlike `extract_images`
+/// which only collects image URLs.
+fn extract_content(element: &ElementRef) -> Vec<ContentPiece> {
+    lazy_static! {
+        static ref COMPONENT_SEL: Selector = Selector::parse(".se_component").unwrap();
+        static ref IMG_SEL: Selector =
+            Selector::parse("img.se_mediaImage, img.img_attachedfile").unwrap();
+    }
+
+    element
+        .select(&COMPONENT_SEL)
+        .filter_map(|component| {
+            if let Some(img) = component.select(&IMG_SEL).next() {
+                normalize_image_url(img.value().attr("data-src")?).map(ContentPiece::Image)
+            } else {
+                let text = component
+                    .text()
+                    .collect::<Vec<_>>()
+                    .join("")
+                    .trim()
+                    .to_owned();
+                if text.is_empty() {
+                    None
+                } else {
+                    Some(ContentPiece::Text(text))
+                }
+            }
+        })
+        .collect()
+}